@@ -1,23 +1,51 @@
+use aho_corasick::AhoCorasickBuilder;
 use clap::{App, Arg};
-use rand::prelude::SliceRandom;
-use rand::{rngs::StdRng, SeedableRng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use regex::{Regex, RegexBuilder};
 use std::{
     error::Error,
     ffi::OsStr,
     fs::{self, File},
-    io::{BufRead, BufReader},
-    path::PathBuf,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
 };
 use walkdir::WalkDir;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+const DAT_DELIM: u8 = b'%';
+
 #[derive(Debug)]
 pub struct Config {
-    sources: Vec<String>,
-    pattern: Option<Regex>,
+    sources: Vec<WeightedSource>,
+    pattern: Option<PatternMatcher>,
     seed: Option<u64>,
+    build_index: bool,
+    length_filter: Option<LengthFilter>,
+}
+
+/// One `fortuner` source argument plus its optional explicit `NN%` weight,
+/// e.g. the `50%`/`off/` pair in `fortuner 50% off/ 50% on/`.
+#[derive(Debug, Clone)]
+struct WeightedSource {
+    pattern: String,
+    percent: Option<u64>,
+}
+
+/// BSD fortune's `-s/--short` and `-l/--long` cutoff, in bytes of fortune text.
+#[derive(Debug, Clone, Copy)]
+enum LengthFilter {
+    Short(u64),
+    Long(u64),
+}
+
+impl LengthFilter {
+    fn allows(self, text: &str) -> bool {
+        match self {
+            LengthFilter::Short(cutoff) => text.len() as u64 <= cutoff,
+            LengthFilter::Long(cutoff) => text.len() as u64 > cutoff,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -26,6 +54,81 @@ struct Fortune {
     text: String,
 }
 
+/// Matches a fortune's text against a set of `-m/--pattern` values.
+///
+/// Plain-literal patterns are folded into a single `aho-corasick` automaton so a
+/// corpus can be scanned for many keywords in one pass; anything containing regex
+/// metacharacters falls back to a compiled `Regex` checked individually.
+#[derive(Debug)]
+struct PatternMatcher {
+    literals: Option<aho_corasick::AhoCorasick>,
+    regexes: Vec<Regex>,
+}
+
+impl PatternMatcher {
+    fn new(patterns: &[String], insensitive: bool) -> MyResult<Self> {
+        let mut literals = vec![];
+        let mut regexes = vec![];
+
+        for pattern in patterns {
+            if is_literal(pattern) {
+                literals.push(pattern.clone());
+            } else {
+                let regex = RegexBuilder::new(pattern)
+                    .case_insensitive(insensitive)
+                    .build()
+                    .map_err(|_| format!("Invalid --pattern \"{}\"", pattern))?;
+                regexes.push(regex);
+            }
+        }
+
+        let literals = if literals.is_empty() {
+            None
+        } else {
+            Some(
+                AhoCorasickBuilder::new()
+                    .ascii_case_insensitive(insensitive)
+                    .build(&literals)?,
+            )
+        };
+
+        Ok(PatternMatcher { literals, regexes })
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        self.literals
+            .as_ref()
+            .is_some_and(|ac| ac.is_match(text))
+            || self.regexes.iter().any(|regex| regex.is_match(text))
+    }
+}
+
+/// A pattern is a "plain literal" if it contains no regex metacharacters, in
+/// which case it's cheaper to fold into the shared Aho-Corasick automaton.
+fn is_literal(pattern: &str) -> bool {
+    !pattern.contains(|c| "\\.^$*+?()[]{}|".contains(c))
+}
+
+/// An in-memory view of a `strfile`-compatible `.dat` index.
+///
+/// `longest`/`shortest` round-trip through the on-disk header for format
+/// compatibility even though fortuner itself doesn't currently consult them.
+#[derive(Debug)]
+struct DatIndex {
+    #[allow(dead_code)]
+    longest: u32,
+    #[allow(dead_code)]
+    shortest: u32,
+    delim: u8,
+    offsets: Vec<u32>,
+}
+
+impl DatIndex {
+    fn count(&self) -> u32 {
+        self.offsets.len() as u32
+    }
+}
+
 pub fn get_args() -> MyResult<Config> {
     let matches = App::new("fortuner")
         .version("0.1.0")
@@ -43,6 +146,8 @@ pub fn get_args() -> MyResult<Config> {
                 .value_name("PATTERN")
                 .short("m")
                 .long("pattern")
+                .multiple(true)
+                .number_of_values(1)
                 .help("Pattern"),
         )
         .arg(
@@ -59,45 +164,140 @@ pub fn get_args() -> MyResult<Config> {
                 .long("seed")
                 .help("Random seed"),
         )
+        .arg(
+            Arg::with_name("build_index")
+                .long("build-index")
+                .help("Build a strfile-compatible .dat index for each source file")
+                .takes_value(false),
+        )
+        .arg(
+            // -s is already --seed, so --short is long-only.
+            Arg::with_name("short")
+                .long("short")
+                .help("Only show fortunes at or under the length cutoff")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("long")
+                .short("l")
+                .long("long")
+                .help("Only show fortunes over the length cutoff")
+                .takes_value(false)
+                .conflicts_with("short"),
+        )
+        .arg(
+            Arg::with_name("length")
+                .value_name("BYTES")
+                .short("n")
+                .long("length")
+                .help("Length cutoff in bytes for --short/--long [default: 160]"),
+        )
         .get_matches();
 
-    let pattern = matches
-        .value_of("pattern")
-        .map(|val| {
-            RegexBuilder::new(val)
-                .case_insensitive(matches.is_present("insensitive"))
-                .build()
-                .map_err(|_| format!("Invalid --pattern \"{}\"", val))
-        })
-        .transpose()?;
+    let patterns = matches.values_of_lossy("pattern").unwrap_or_default();
+    let pattern = if patterns.is_empty() {
+        None
+    } else {
+        Some(PatternMatcher::new(
+            &patterns,
+            matches.is_present("insensitive"),
+        )?)
+    };
+
+    let cutoff = matches
+        .value_of("length")
+        .map(parse_u64)
+        .transpose()?
+        .unwrap_or(160);
+    let length_filter = if matches.is_present("short") {
+        Some(LengthFilter::Short(cutoff))
+    } else if matches.is_present("long") {
+        Some(LengthFilter::Long(cutoff))
+    } else {
+        None
+    };
 
     Ok(Config {
-        sources: matches.values_of_lossy("sources").unwrap(),
+        sources: parse_weighted_sources(&matches.values_of_lossy("sources").unwrap())?,
         seed: matches.value_of("seed").map(parse_u64).transpose()?,
         pattern,
+        build_index: matches.is_present("build_index"),
+        length_filter,
     })
 }
 
+/// Parses `fortuner`'s positional `sources`, pulling any `NN%` token into the
+/// weight of the source argument that immediately follows it.
+fn parse_weighted_sources(tokens: &[String]) -> MyResult<Vec<WeightedSource>> {
+    let mut sources = vec![];
+    let mut pending_percent = None;
+
+    for token in tokens {
+        if let Some(percent) = parse_percent(token) {
+            if pending_percent.is_some() {
+                return Err(format!("Expected a source to follow \"{}\"", token).into());
+            }
+            pending_percent = Some(percent);
+        } else {
+            sources.push(WeightedSource {
+                pattern: token.clone(),
+                percent: pending_percent.take(),
+            });
+        }
+    }
+
+    if pending_percent.is_some() {
+        return Err("Expected a source to follow the trailing percentage".into());
+    }
+
+    Ok(sources)
+}
+
+/// Parses a bare `NN%` token, e.g. `"50%"` -> `Some(50)`. Parses into a wide
+/// integer so an out-of-range percent like `300%` is still recognized as a
+/// percentage instead of silently falling through as a literal source path.
+/// Percentages are only summed and checked against 100 in `pick_fortune`;
+/// `--pattern` and `--build-index` runs ignore them.
+fn parse_percent(token: &str) -> Option<u64> {
+    token.strip_suffix('%').and_then(|digits| digits.parse().ok())
+}
+
 pub fn run(config: Config) -> MyResult<()> {
-    let files = find_files(&config.sources)?;
-    let fortunes = read_fortunes(&files)?;
+    let patterns: Vec<String> =
+        config.sources.iter().map(|source| source.pattern.clone()).collect();
+    let files = find_files(&patterns)?;
+
+    if config.build_index {
+        for file in &files {
+            build_index(file)?;
+        }
+        return Ok(());
+    }
 
     if let Some(pattern) = config.pattern {
+        let fortunes = read_fortunes(&files)?;
         let mut prev_source = None;
-        for fortune in fortunes
-            .iter()
-            .filter(|fortune| pattern.is_match(&fortune.text))
-        {
+        let mut any_matched = false;
+        for fortune in fortunes.iter().filter(|fortune| {
+            pattern.is_match(&fortune.text)
+                && config
+                    .length_filter
+                    .is_none_or(|filter| filter.allows(&fortune.text))
+        }) {
+            any_matched = true;
             if prev_source.as_ref().map_or(true, |s| s != &fortune.source) {
                 eprintln!("({})\n%", fortune.source);
                 prev_source = Some(fortune.source.clone());
             }
             println!("{}\n%", fortune.text);
         }
+        if !any_matched {
+            println!("No fortunes found");
+        }
     } else {
         println!(
             "{}",
-            pick_fortune(&fortunes, config.seed)
+            pick_fortune(&config.sources, config.seed, config.length_filter)?
                 .or_else(|| Some("No fortunes found".to_string()))
                 .unwrap()
         );
@@ -112,23 +312,10 @@ fn parse_u64(val: &str) -> MyResult<u64> {
 }
 
 fn find_files(paths: &[String]) -> MyResult<Vec<PathBuf>> {
-    let dat = OsStr::new("dat");
     let mut files = vec![];
 
     for path in paths {
-        match fs::metadata(path) {
-            Err(e) => return Err(format!("{}: {}", path, e).into()),
-            Ok(_) => files.extend(
-                WalkDir::new(path)
-                    .into_iter()
-                    .filter_map(Result::ok)
-                    .filter(|e| {
-                        e.file_type().is_file()
-                            && e.path().extension() != Some(dat)
-                    })
-                    .map(|e| e.path().into()),
-            ),
-        }
+        files.extend(find_files_for_source(path)?);
     }
 
     files.sort();
@@ -136,41 +323,550 @@ fn find_files(paths: &[String]) -> MyResult<Vec<PathBuf>> {
     Ok(files)
 }
 
+/// Resolves a single source argument (literal path or glob) to its files.
+fn find_files_for_source(path: &str) -> MyResult<Vec<PathBuf>> {
+    let dat = OsStr::new("dat");
+
+    if is_glob(path) {
+        let glob = glob_to_regex(path)?;
+        let root = nearest_existing_ancestor(Path::new(path))
+            .map_err(|e| format!("{}: {}", path, e))?;
+        // Walking the implied "." root of a bare glob yields "./name" paths,
+        // which the glob itself has no component for.
+        let bare = root == Path::new(".") && !path.starts_with("./");
+        return Ok(WalkDir::new(root)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| {
+                let candidate = if bare {
+                    e.path().strip_prefix(".").unwrap_or(e.path())
+                } else {
+                    e.path()
+                };
+                e.file_type().is_file()
+                    && e.path().extension() != Some(dat)
+                    && glob.is_match(&candidate.to_string_lossy())
+            })
+            .map(|e| e.path().into())
+            .collect());
+    }
+
+    match fs::metadata(path) {
+        Err(e) => Err(format!("{}: {}", path, e).into()),
+        Ok(_) => Ok(WalkDir::new(path)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file() && e.path().extension() != Some(dat))
+            .map(|e| e.path().into())
+            .collect()),
+    }
+}
+
+/// Whether `path` contains shell-glob metacharacters (`*` or `?`) this module translates.
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?'])
+}
+
+/// Nearest ancestor directory of `path` that actually exists, used as the root
+/// to walk when `path` itself is a glob pattern rather than a literal path.
+/// Errors rather than silently walking the current directory when `path` has
+/// a directory component and none of it exists; a bare glob with no
+/// directory component (e.g. `*.txt`) still walks the current directory, same
+/// as the shell would expand it there.
+fn nearest_existing_ancestor(path: &Path) -> MyResult<PathBuf> {
+    if path.parent().is_none_or(|parent| parent.as_os_str().is_empty()) {
+        return Ok(PathBuf::from("."));
+    }
+
+    path.ancestors()
+        .find(|ancestor| ancestor.is_dir())
+        .map(Path::to_path_buf)
+        .ok_or_else(|| "No such file or directory".into())
+}
+
+/// Translates a shell glob into an anchored `Regex`, mirroring the approach
+/// used by tools like MOROS/mercurial: escape regex-significant characters,
+/// then map `*` to `[^/]*`, `**` to `.*`, and `?` to `[^/]`.
+fn glob_to_regex(pattern: &str) -> MyResult<Regex> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut regex_str = String::from("^");
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                regex_str.push_str(".*");
+                i += 1;
+            }
+            '*' => regex_str.push_str("[^/]*"),
+            '?' => regex_str.push_str("[^/]"),
+            c if r"\.+^$(){}|[]".contains(c) => {
+                regex_str.push('\\');
+                regex_str.push(c);
+            }
+            c => regex_str.push(c),
+        }
+        i += 1;
+    }
+
+    regex_str.push('$');
+    RegexBuilder::new(&regex_str)
+        .build()
+        .map_err(|_| format!("Invalid glob \"{}\"", pattern).into())
+}
+
 fn read_fortunes(paths: &[PathBuf]) -> MyResult<Vec<Fortune>> {
     let mut fortunes = vec![];
-    let mut buffer = vec![];
 
     for path in paths {
-        let basename =
-            path.file_name().unwrap().to_string_lossy().into_owned();
-        let file = File::open(path).map_err(|e| {
-            format!("{}: {}", path.to_string_lossy().into_owned(), e)
-        })?;
-
-        for line in BufReader::new(file).lines().filter_map(Result::ok) {
-            if line == "%" {
-                if !buffer.is_empty() {
-                    fortunes.push(Fortune {
-                        source: basename.clone(),
-                        text: buffer.join("\n"),
-                    });
-                    buffer.clear();
-                }
-            } else {
-                buffer.push(line.to_string());
+        fortunes.extend(read_fortunes_from_file(path)?);
+    }
+
+    Ok(fortunes)
+}
+
+fn read_fortunes_from_file(path: &Path) -> MyResult<Vec<Fortune>> {
+    let mut fortunes = vec![];
+    let mut buffer = vec![];
+    let basename = path.file_name().unwrap().to_string_lossy().into_owned();
+    let file = File::open(path)
+        .map_err(|e| format!("{}: {}", path.to_string_lossy().into_owned(), e))?;
+
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        if line == "%" {
+            if !buffer.is_empty() {
+                fortunes.push(Fortune {
+                    source: basename.clone(),
+                    text: buffer.join("\n"),
+                });
+                buffer.clear();
             }
+        } else {
+            buffer.push(line.to_string());
         }
     }
 
+    if !buffer.is_empty() {
+        fortunes.push(Fortune {
+            source: basename,
+            text: buffer.join("\n"),
+        });
+    }
+
     Ok(fortunes)
 }
 
-fn pick_fortune(fortunes: &[Fortune], seed: Option<u64>) -> Option<String> {
-    if let Some(val) = seed {
-        let mut rng = StdRng::seed_from_u64(val);
-        fortunes.choose(&mut rng).map(|f| f.text.to_string())
-    } else {
-        let mut rng = rand::thread_rng();
-        fortunes.choose(&mut rng).map(|f| f.text.to_string())
+/// Path of the `strfile`-compatible index sibling to `path`, e.g. `jokes` -> `jokes.dat`.
+fn dat_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".dat");
+    PathBuf::from(name)
+}
+
+/// Writes a `<file>.dat` index of fortune start offsets for `path`.
+fn build_index(path: &Path) -> MyResult<()> {
+    let file = File::open(path)
+        .map_err(|e| format!("{}: {}", path.to_string_lossy().into_owned(), e))?;
+    let mut reader = BufReader::new(file);
+
+    let mut offsets = vec![];
+    let mut longest = 0u32;
+    let mut shortest = u32::MAX;
+    let mut pos = 0u64;
+    let mut start = 0u64;
+    // Sum of each line's length plus the count of lines seen so far, so the
+    // final text length can be computed as `text_len + (line_count - 1)` --
+    // matching `buffer.join("\n")`'s single `\n` *between* lines rather than
+    // after every line.
+    let mut text_len = 0u64;
+    let mut line_count = 0u64;
+    let mut in_fortune = false;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        let bytes_read = reader.read_line(&mut line)? as u64;
+        if bytes_read == 0 {
+            break;
+        }
+
+        let trimmed = line.trim_end_matches(['\n', '\r']);
+        if trimmed == "%" {
+            if in_fortune {
+                offsets.push(start as u32);
+                let len = text_len + (line_count - 1);
+                longest = longest.max(len as u32);
+                shortest = shortest.min(len as u32);
+                in_fortune = false;
+            }
+        } else {
+            if !in_fortune {
+                start = pos;
+                text_len = 0;
+                line_count = 0;
+                in_fortune = true;
+            }
+            text_len += trimmed.len() as u64;
+            line_count += 1;
+        }
+
+        pos += bytes_read;
     }
-}
\ No newline at end of file
+
+    if in_fortune {
+        offsets.push(start as u32);
+        let len = text_len + (line_count - 1);
+        longest = longest.max(len as u32);
+        shortest = shortest.min(len as u32);
+    }
+
+    if offsets.is_empty() {
+        shortest = 0;
+    }
+
+    let mut out = File::create(dat_path(path))?;
+    out.write_all(&(offsets.len() as u32).to_be_bytes())?;
+    out.write_all(&longest.to_be_bytes())?;
+    out.write_all(&shortest.to_be_bytes())?;
+    out.write_all(&(DAT_DELIM as u32).to_be_bytes())?;
+    for offset in &offsets {
+        out.write_all(&offset.to_be_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Loads the `.dat` index for `path` if one exists and is at least as new as `path` itself.
+fn read_valid_index(path: &Path) -> MyResult<Option<DatIndex>> {
+    let dat = dat_path(path);
+    if !dat.exists() {
+        return Ok(None);
+    }
+
+    let source_mtime = fs::metadata(path)?.modified()?;
+    let dat_mtime = fs::metadata(&dat)?.modified()?;
+    if dat_mtime < source_mtime {
+        return Ok(None);
+    }
+
+    let mut file = File::open(&dat)?;
+    let mut header = [0u8; 16];
+    if file.read_exact(&mut header).is_err() {
+        return Ok(None);
+    }
+
+    let count = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    let longest = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    let shortest = u32::from_be_bytes(header[8..12].try_into().unwrap());
+    let delim = u32::from_be_bytes(header[12..16].try_into().unwrap()) as u8;
+
+    let mut offsets = Vec::with_capacity(count as usize);
+    let mut buf = [0u8; 4];
+    for _ in 0..count {
+        if file.read_exact(&mut buf).is_err() {
+            return Ok(None);
+        }
+        offsets.push(u32::from_be_bytes(buf));
+    }
+
+    Ok(Some(DatIndex {
+        longest,
+        shortest,
+        delim,
+        offsets,
+    }))
+}
+
+/// Seeks to the `local_index`-th fortune recorded in `index` and reads just that cookie.
+fn read_cookie_at(path: &Path, index: &DatIndex, local_index: u32) -> MyResult<String> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(index.offsets[local_index as usize] as u64))?;
+
+    let delim_line = (index.delim as char).to_string();
+    let mut buffer = vec![];
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        if line == delim_line {
+            break;
+        }
+        buffer.push(line);
+    }
+
+    Ok(buffer.join("\n"))
+}
+
+enum FortuneSource {
+    Indexed(PathBuf, DatIndex),
+    Scanned(Vec<Fortune>),
+}
+
+/// All the fortunes backing one source argument, and the weight with which
+/// that argument's bucket is drawn from in [`pick_fortune`].
+struct Bucket {
+    sources: Vec<FortuneSource>,
+    count: u32,
+    percent: Option<u64>,
+}
+
+/// Loads (or indexes into) every file behind a single source argument.
+fn load_bucket(source: &WeightedSource, length_filter: Option<LengthFilter>) -> MyResult<Bucket> {
+    let mut fortune_sources = vec![];
+    let mut count = 0u32;
+
+    for file in find_files_for_source(&source.pattern)? {
+        // The index only records offsets, not per-fortune length, so a length
+        // filter forces a full scan of the file's text.
+        let index = if length_filter.is_none() {
+            read_valid_index(&file)?
+        } else {
+            None
+        };
+
+        match index {
+            Some(index) => {
+                count += index.count();
+                fortune_sources.push(FortuneSource::Indexed(file, index));
+            }
+            None => {
+                let fortunes: Vec<Fortune> = read_fortunes_from_file(&file)?
+                    .into_iter()
+                    .filter(|fortune| {
+                        length_filter.is_none_or(|filter| filter.allows(&fortune.text))
+                    })
+                    .collect();
+                count += fortunes.len() as u32;
+                fortune_sources.push(FortuneSource::Scanned(fortunes));
+            }
+        }
+    }
+
+    Ok(Bucket {
+        sources: fortune_sources,
+        count,
+        percent: source.percent,
+    })
+}
+
+/// Picks one fortune with a BSD-fortune-style two-stage draw: first a source
+/// bucket is chosen by weight (an explicit `NN%`, an even share of whatever
+/// percentage is left over, or by default its own fortune count so unweighted
+/// sources behave exactly like one flattened pool), then a fortune is chosen
+/// uniformly within that bucket.
+fn pick_fortune(
+    sources: &[WeightedSource],
+    seed: Option<u64>,
+    length_filter: Option<LengthFilter>,
+) -> MyResult<Option<String>> {
+    let buckets = sources
+        .iter()
+        .map(|source| load_bucket(source, length_filter))
+        .collect::<MyResult<Vec<_>>>()?;
+
+    let explicit_total: u64 = buckets.iter().filter_map(|b| b.percent).sum();
+    if explicit_total > 100 {
+        return Err(format!(
+            "Source percentages must sum to 100 or less (got {})",
+            explicit_total
+        )
+        .into());
+    }
+
+    let any_explicit = buckets.iter().any(|b| b.percent.is_some());
+    // Buckets emptied by the length filter get zero weight below, so they
+    // must not count against the denominator here either, or the leftover
+    // percentage would be under-distributed among the buckets that survive.
+    let unspecified = buckets
+        .iter()
+        .filter(|b| b.percent.is_none() && b.count > 0)
+        .count();
+    let remaining = 100 - explicit_total;
+
+    // A bucket with no fortunes left (e.g. `--short`/`--long` filtered an
+    // explicitly weighted source down to nothing) must never be drawn, so
+    // its weight is forced to zero and the rest of the pool absorbs its
+    // share proportionally, rather than failing the whole pick.
+    let weights: Vec<f64> = buckets
+        .iter()
+        .map(|bucket| {
+            if bucket.count == 0 {
+                return 0.0;
+            }
+            match bucket.percent {
+                Some(percent) => percent as f64,
+                None if any_explicit && unspecified > 0 => remaining as f64 / unspecified as f64,
+                None => bucket.count as f64,
+            }
+        })
+        .collect();
+
+    let total_weight: f64 = weights.iter().sum();
+    if total_weight <= 0.0 {
+        return Ok(None);
+    }
+
+    let mut rng = match seed {
+        Some(val) => StdRng::seed_from_u64(val),
+        None => StdRng::from_rng(rand::thread_rng())?,
+    };
+
+    let mut bucket_choice = rng.gen_range(0.0..total_weight);
+    let mut chosen = None;
+    for (bucket, weight) in buckets.into_iter().zip(weights) {
+        if bucket_choice < weight {
+            chosen = Some(bucket);
+            break;
+        }
+        bucket_choice -= weight;
+    }
+
+    // Zero-weight (empty) buckets can never win the draw above, so this is
+    // just a defensive backstop against an empty `chosen`.
+    let bucket = match chosen {
+        Some(bucket) if bucket.count > 0 => bucket,
+        _ => return Ok(None),
+    };
+
+    let mut choice = rng.gen_range(0..bucket.count);
+    for source in bucket.sources {
+        match source {
+            FortuneSource::Indexed(path, index) => {
+                let count = index.count();
+                if choice < count {
+                    return Ok(Some(read_cookie_at(&path, &index, choice)?));
+                }
+                choice -= count;
+            }
+            FortuneSource::Scanned(fortunes) => {
+                let count = fortunes.len() as u32;
+                if choice < count {
+                    return Ok(Some(fortunes[choice as usize].text.clone()));
+                }
+                choice -= count;
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    /// Unique scratch file under the system temp dir for a single test.
+    fn scratch_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("fortuner_test_{}_{}", std::process::id(), name))
+    }
+
+    fn write_fortunes(path: &Path, fortunes: &[&str]) {
+        let mut body = String::new();
+        for fortune in fortunes {
+            body.push_str(fortune);
+            body.push_str("\n%\n");
+        }
+        fs::write(path, body).unwrap();
+    }
+
+    #[test]
+    fn index_round_trips_through_read_cookie_at() {
+        let path = scratch_path("index_round_trip");
+        write_fortunes(&path, &["A little nonsense now and then", "Keep it simple"]);
+
+        build_index(&path).unwrap();
+        let index = read_valid_index(&path).unwrap().expect("index should exist");
+        assert_eq!(index.count(), 2);
+
+        let expected = read_fortunes_from_file(&path).unwrap();
+        for (i, fortune) in expected.iter().enumerate() {
+            let cookie = read_cookie_at(&path, &index, i as u32).unwrap();
+            assert_eq!(cookie, fortune.text);
+        }
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(dat_path(&path)).unwrap();
+    }
+
+    #[test]
+    fn index_header_records_exact_cookie_lengths() {
+        let path = scratch_path("index_lengths");
+        write_fortunes(&path, &["hello", "multi\nline\nfortune"]);
+
+        build_index(&path).unwrap();
+        let index = read_valid_index(&path).unwrap().expect("index should exist");
+
+        let expected = read_fortunes_from_file(&path).unwrap();
+        let longest = expected.iter().map(|f| f.text.len() as u32).max().unwrap();
+        let shortest = expected.iter().map(|f| f.text.len() as u32).min().unwrap();
+        assert_eq!(index.longest, longest);
+        assert_eq!(index.shortest, shortest);
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(dat_path(&path)).unwrap();
+    }
+
+    #[test]
+    fn missing_index_falls_back_to_none() {
+        let path = scratch_path("missing_index");
+        write_fortunes(&path, &["No index for this one"]);
+
+        assert!(read_valid_index(&path).unwrap().is_none());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn stale_index_falls_back_to_none() {
+        let path = scratch_path("stale_index");
+        write_fortunes(&path, &["Original"]);
+        build_index(&path).unwrap();
+
+        // Simulate the source file changing after the index was built.
+        let future = SystemTime::now() + Duration::from_secs(60);
+        write_fortunes(&path, &["Original", "Added after indexing"]);
+        File::open(&path).unwrap().set_modified(future).unwrap();
+
+        assert!(read_valid_index(&path).unwrap().is_none());
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(dat_path(&path)).unwrap();
+    }
+
+    #[test]
+    fn pick_fortune_skips_a_weighted_bucket_emptied_by_the_length_filter() {
+        let empty_path = scratch_path("empty_bucket");
+        let full_path = scratch_path("full_bucket");
+        write_fortunes(&empty_path, &["This one is far too long to survive a tiny cutoff"]);
+        write_fortunes(&full_path, &["short"]);
+
+        let sources = vec![
+            WeightedSource { pattern: empty_path.to_string_lossy().into_owned(), percent: Some(90) },
+            WeightedSource { pattern: full_path.to_string_lossy().into_owned(), percent: Some(10) },
+        ];
+
+        for seed in 0..20 {
+            let picked = pick_fortune(&sources, Some(seed), Some(LengthFilter::Short(5)))
+                .unwrap()
+                .expect("the non-empty bucket should still be reachable");
+            assert_eq!(picked, "short");
+        }
+
+        fs::remove_file(&empty_path).unwrap();
+        fs::remove_file(&full_path).unwrap();
+    }
+
+    #[test]
+    fn glob_to_regex_matches_expected_paths() {
+        let re = glob_to_regex("fortunes/*-o").unwrap();
+        assert!(re.is_match("fortunes/quotes-o"));
+        assert!(!re.is_match("fortunes/nested/quotes-o"));
+
+        let re = glob_to_regex("data/**/wisdom?").unwrap();
+        assert!(re.is_match("data/a/b/wisdom1"));
+    }
+
+    #[test]
+    fn nearest_existing_ancestor_errors_on_a_missing_directory() {
+        assert!(nearest_existing_ancestor(Path::new("no/such/directory/*.txt")).is_err());
+        assert!(nearest_existing_ancestor(Path::new("*.txt")).is_ok());
+    }
+}